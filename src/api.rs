@@ -1,6 +1,58 @@
-use anyhow::{bail, Context, Result};
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+
+use governor::{DefaultDirectRateLimiter, Quota};
 use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+use thiserror::Error;
+
+/// DigitalOcean's published per-token rate limit, used when the caller
+/// doesn't configure a different quota.
+pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 250;
+
+/// Errors returned by [`ApiClient`], distinguishing transport failures from
+/// the DigitalOcean API's own typed errors.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("failed to send request to the DigitalOcean API")]
+    Request(#[from] reqwest::Error),
+
+    #[error("failed to parse DigitalOcean API response")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("DigitalOcean API error ({id}): {message}")]
+    Api { id: String, message: String },
+
+    #[error("DigitalOcean API rate limit exceeded, resets at unix time {reset}")]
+    RateLimited { remaining: u32, reset: u64 },
+}
+
+/// The `Ratelimit-Remaining`/`Ratelimit-Reset` headers DigitalOcean attaches
+/// to every API response.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimit {
+    pub remaining: u32,
+    pub reset: u64,
+}
+
+fn parse_rate_limit(headers: &HeaderMap) -> Option<RateLimit> {
+    let remaining = headers
+        .get("Ratelimit-Remaining")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    let reset = headers
+        .get("Ratelimit-Reset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(RateLimit { remaining, reset })
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
@@ -25,23 +77,36 @@ enum DomainRecordsResponse {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Tabled)]
 pub struct DomainRecord {
     pub id: i64,
     pub name: String,
-    pub data: String,
-    pub ttl: i32,
 
     #[serde(rename = "type")]
+    #[tabled(rename = "type")]
     pub kind: String,
+
+    pub data: String,
+    pub ttl: i32,
 }
 
 pub struct ApiClient {
     http: reqwest::Client,
+    rate_limit: Mutex<Option<RateLimit>>,
+    limiter: DefaultDirectRateLimiter,
 }
 
 impl ApiClient {
     pub fn new(token: &str) -> Self {
+        Self::with_requests_per_minute(
+            token,
+            NonZeroU32::new(DEFAULT_REQUESTS_PER_MINUTE).unwrap(),
+        )
+    }
+
+    /// Like [`ApiClient::new`], but gates requests through `requests_per_minute`
+    /// instead of DigitalOcean's published default.
+    pub fn with_requests_per_minute(token: &str, requests_per_minute: NonZeroU32) -> Self {
         let mut headers = HeaderMap::new();
         headers.insert(
             ACCEPT,
@@ -60,16 +125,55 @@ impl ApiClient {
                 .default_headers(headers)
                 .build()
                 .expect("Failed to build HTTP client"),
+            rate_limit: Mutex::new(None),
+            limiter: DefaultDirectRateLimiter::direct(Quota::per_minute(requests_per_minute)),
         }
     }
 
+    /// The rate limit DigitalOcean reported on the most recently completed
+    /// request, if any has completed yet.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// Consumes a quota slot if one is available right now, without waiting.
+    /// Returns whether it was.
+    #[cfg(test)]
+    fn try_acquire(&self) -> bool {
+        self.limiter.check().is_ok()
+    }
+
+    /// Sends `request`, records the `Ratelimit-*` response headers, and
+    /// deserializes the body as `T`. Returns [`ApiError::RateLimited`] on an
+    /// HTTP 429 that carries both `Ratelimit-*` headers instead of attempting
+    /// to parse the body; a 429 without them has its body parsed like any
+    /// other response.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ApiError> {
+        self.limiter.until_ready().await;
+        let response = request.send().await?;
+
+        if let Some(rate_limit) = parse_rate_limit(response.headers()) {
+            *self.rate_limit.lock().unwrap() = Some(rate_limit);
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                return Err(ApiError::RateLimited {
+                    remaining: rate_limit.remaining,
+                    reset: rate_limit.reset,
+                });
+            }
+        }
+
+        let body = response.text().await?;
+        log::debug!("response body: {body}");
+        Ok(serde_json::from_str(&body)?)
+    }
+
     pub async fn get_records(
         &self,
         domain: &str,
         per_page: Option<u16>,
         kind: Option<&str>,
         name: Option<&str>,
-    ) -> Result<Vec<DomainRecord>> {
+    ) -> Result<Vec<DomainRecord>, ApiError> {
         let mut url = format!("https://api.digitalocean.com/v2/domains/{domain}/records");
 
         let mut params = Vec::new();
@@ -87,22 +191,9 @@ impl ApiClient {
             url.push_str(&params.join("&"));
         }
 
-        let response = self
-            .http
-            .get(url)
-            .send()
-            .await
-            .context("Failed to send GET request (get_records)")?
-            .text()
-            .await
-            .context("Failed to fetch GET response (get_records)")?;
-        log::debug!("get_records: {response}");
-
-        let response = serde_json::from_str(&response)
-            .context("Failed to parse GET response (get_records)")?;
-        match response {
+        match self.send::<DomainRecordsResponse>(self.http.get(url)).await? {
             DomainRecordsResponse::Ok { domain_records, .. } => Ok(domain_records),
-            DomainRecordsResponse::Error { id, message } => bail!("{}: {}", id, message),
+            DomainRecordsResponse::Error { id, message } => Err(ApiError::Api { id, message }),
         }
     }
 
@@ -110,12 +201,12 @@ impl ApiClient {
         &self,
         domain: &str,
         id: i64,
-        kind: &str,
         name: &str,
+        kind: &str,
         data: &str,
         ttl: u32,
-    ) -> Result<DomainRecord> {
-        let response = self
+    ) -> Result<DomainRecord, ApiError> {
+        let request = self
             .http
             .put(format!(
                 "https://api.digitalocean.com/v2/domains/{domain}/records/{id}"
@@ -125,20 +216,11 @@ impl ApiClient {
                 "type": kind,
                 "data": data,
                 "ttl": ttl,
-            }))
-            .send()
-            .await
-            .context("Failed to send PUT request (update_record)")?
-            .text()
-            .await
-            .context("Failed to fetch PUT response (update_record)")?;
-        log::debug!("update_record: {response}");
-
-        let response = serde_json::from_str(&response)
-            .context("Failed to parse PUT response (create_record)")?;
-        match response {
+            }));
+
+        match self.send::<DomainRecordResponse>(request).await? {
             DomainRecordResponse::Ok { domain_record } => Ok(domain_record),
-            DomainRecordResponse::Error { id, message } => bail!("{}: {}", id, message),
+            DomainRecordResponse::Error { id, message } => Err(ApiError::Api { id, message }),
         }
     }
 
@@ -149,8 +231,8 @@ impl ApiClient {
         kind: &str,
         data: &str,
         ttl: u32,
-    ) -> Result<DomainRecord> {
-        let response = self
+    ) -> Result<DomainRecord, ApiError> {
+        let request = self
             .http
             .post(format!(
                 "https://api.digitalocean.com/v2/domains/{domain}/records/"
@@ -160,20 +242,71 @@ impl ApiClient {
                 "type": kind,
                 "data": data,
                 "ttl": ttl,
-            }))
-            .send()
-            .await
-            .context("Failed to send POST request (create_record)")?
-            .text()
-            .await
-            .context("Failed to fetch POST response (create_record)")?;
-        log::debug!("create_record: {response}");
-
-        let response = serde_json::from_str(&response)
-            .context("Failed to parse POST response (create_record)")?;
-        match response {
+            }));
+
+        match self.send::<DomainRecordResponse>(request).await? {
             DomainRecordResponse::Ok { domain_record } => Ok(domain_record),
-            DomainRecordResponse::Error { id, message } => bail!("{}: {}", id, message),
+            DomainRecordResponse::Error { id, message } => Err(ApiError::Api { id, message }),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Ratelimit-Remaining", "239".parse().unwrap());
+        headers.insert("Ratelimit-Reset", "1700000000".parse().unwrap());
+
+        let rate_limit = parse_rate_limit(&headers).unwrap();
+        assert_eq!(rate_limit.remaining, 239);
+        assert_eq!(rate_limit.reset, 1700000000);
+    }
+
+    #[test]
+    fn missing_rate_limit_headers_parse_to_none() {
+        assert!(parse_rate_limit(&HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn rate_limited_error_reports_reset_time() {
+        let error = ApiError::RateLimited {
+            remaining: 0,
+            reset: 1700000000,
+        };
+        assert_eq!(
+            error.to_string(),
+            "DigitalOcean API rate limit exceeded, resets at unix time 1700000000"
+        );
+    }
+
+    #[test]
+    fn default_requests_per_minute_is_a_valid_quota() {
+        assert!(NonZeroU32::new(DEFAULT_REQUESTS_PER_MINUTE).is_some());
+    }
+
+    #[test]
+    fn new_and_custom_quota_clients_start_with_no_known_rate_limit() {
+        let client = ApiClient::new("token");
+        assert!(client.rate_limit().is_none());
+
+        let client =
+            ApiClient::with_requests_per_minute("token", NonZeroU32::new(10).unwrap());
+        assert!(client.rate_limit().is_none());
+    }
+
+    #[test]
+    fn limiter_blocks_once_its_burst_is_exhausted() {
+        let client =
+            ApiClient::with_requests_per_minute("token", NonZeroU32::new(1).unwrap());
+
+        assert!(client.try_acquire(), "the first request should fit the burst");
+        assert!(
+            !client.try_acquire(),
+            "a second immediate request should be blocked by the quota"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// DNS record types `[[record]]` can manage. Limited to the two address
+/// types `dyndns` actually knows how to create and update; CNAME/TXT have
+/// no associated target data and no write path, so they're left out until
+/// those exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordKind {
+    A,
+    Aaaa,
+}
+
+impl RecordKind {
+    /// The string DigitalOcean expects in the `type` field of its API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordKind::A => "A",
+            RecordKind::Aaaa => "AAAA",
+        }
+    }
+}
+
+/// A single managed record, as configured in a `[[record]]` table.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordConfig {
+    pub domain: String,
+
+    #[serde(default = "default_subdomain")]
+    pub subdomain: String,
+
+    #[serde(rename = "type")]
+    pub kind: RecordKind,
+
+    #[serde(default = "default_ttl")]
+    pub ttl: u32,
+}
+
+impl RecordConfig {
+    /// Only `A` records track the host's IPv4 address.
+    pub fn wants_ipv4(&self) -> bool {
+        self.kind == RecordKind::A
+    }
+
+    /// Only `AAAA` records track the host's IPv6 address.
+    pub fn wants_ipv6(&self) -> bool {
+        self.kind == RecordKind::Aaaa
+    }
+}
+
+fn default_subdomain() -> String {
+    "@".to_string()
+}
+
+fn default_ttl() -> u32 {
+    60
+}
+
+/// SMTP settings for the optional `[notify]` section.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotifyConfig {
+    pub server: String,
+
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: String,
+}
+
+fn default_smtp_port() -> u16 {
+    465
+}
+
+/// Top-level `do_dyndns` configuration, deserialized from a TOML file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    #[serde(rename = "record")]
+    pub records: Vec<RecordConfig>,
+
+    pub notify: Option<NotifyConfig>,
+
+    /// Overrides the DigitalOcean API quota (requests per minute) [default: DigitalOcean's published per-token limit]
+    pub requests_per_minute: Option<std::num::NonZeroU32>,
+
+    /// Detect the public IP via DNS instead of the HTTP providers, falling back to HTTP on failure
+    #[serde(default)]
+    pub dns_ip_detection: bool,
+
+    /// Confirm a written record has propagated to its authoritative nameservers
+    #[serde(default)]
+    pub verify_propagation: bool,
+
+    /// How long to keep retrying the authoritative lookup before giving up
+    #[serde(default = "default_verify_propagation_timeout_secs")]
+    pub verify_propagation_timeout_secs: u64,
+}
+
+fn default_verify_propagation_timeout_secs() -> u64 {
+    30
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn defaults_subdomain_and_ttl() {
+        let config: Config = toml::from_str(
+            r#"
+            [[record]]
+            domain = "example.com"
+            type = "A"
+            "#,
+        )
+        .unwrap();
+
+        let record = &config.records[0];
+        assert_eq!(record.subdomain, "@");
+        assert_eq!(record.ttl, 60);
+        assert!(record.wants_ipv4());
+        assert!(!record.wants_ipv6());
+    }
+
+    #[test]
+    fn aaaa_record_wants_ipv6_only() {
+        let config: Config = toml::from_str(
+            r#"
+            [[record]]
+            domain = "example.com"
+            type = "AAAA"
+            "#,
+        )
+        .unwrap();
+
+        let record = &config.records[0];
+        assert!(!record.wants_ipv4());
+        assert!(record.wants_ipv6());
+    }
+
+    #[test]
+    fn unsupported_record_type_fails_to_parse() {
+        let result: Result<Config, _> = toml::from_str(
+            r#"
+            [[record]]
+            domain = "example.com"
+            type = "CNAME"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn optional_sections_default_to_disabled() {
+        let config: Config = toml::from_str(
+            r#"
+            [[record]]
+            domain = "example.com"
+            type = "A"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.notify.is_none());
+        assert!(config.requests_per_minute.is_none());
+        assert!(!config.dns_ip_detection);
+        assert!(!config.verify_propagation);
+        assert_eq!(config.verify_propagation_timeout_secs, 30);
+    }
+}
@@ -5,17 +5,46 @@ use std::{
 };
 
 use anyhow::{Context, Result};
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
 use reqwest::{Client, IntoUrl};
 
 static PROVIDERS: [&str; 2] = ["https://ifconfig.me", "https://ifconfig.co"];
 
+/// OpenDNS resolvers that answer a `myip.opendns.com` query with the
+/// caller's own address.
+const OPENDNS_V4: [Ipv4Addr; 2] = [
+    Ipv4Addr::new(208, 67, 222, 222),
+    Ipv4Addr::new(208, 67, 220, 220),
+];
+const OPENDNS_V6: Ipv6Addr = Ipv6Addr::new(0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35);
+
+/// `ns1.google.com`'s addresses, pinned so it can be queried directly
+/// instead of resolved (which would defeat the point of bypassing recursive
+/// DNS). The IPv6 address is needed so the TXT query for an IPv6 address
+/// isn't sent over an IPv4 transport.
+const GOOGLE_NS1_V4: Ipv4Addr = Ipv4Addr::new(216, 239, 32, 10);
+const GOOGLE_NS1_V6: Ipv6Addr = Ipv6Addr::new(0x2001, 0x4860, 0x4802, 0x32, 0, 0, 0, 0xa);
+
 pub async fn get_ips(
     get_ipv4: bool,
     get_ipv6: bool,
+    use_dns: bool,
 ) -> Result<(Option<Ipv4Addr>, Option<Ipv6Addr>)> {
     let mut ipv4 = None;
     let mut ipv6 = None;
 
+    if use_dns {
+        if get_ipv4 {
+            ipv4 = try_get_ipv4_via_dns().await;
+        }
+        if get_ipv6 {
+            ipv6 = try_get_ipv6_via_dns().await;
+        }
+    }
+
     let ipv4_client = reqwest::Client::builder()
         .timeout(Duration::from_secs(10))
         .local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
@@ -28,6 +57,10 @@ pub async fn get_ips(
         .expect("Failed to build IPv6 client");
 
     for provider in PROVIDERS {
+        if (get_ipv4 == ipv4.is_some()) && (get_ipv6 == ipv6.is_some()) {
+            break;
+        }
+
         log::debug!("Trying provider {provider}");
 
         if get_ipv4 && ipv4.is_none() {
@@ -44,13 +77,97 @@ pub async fn get_ips(
                 _ => (),
             }
         }
+    }
 
-        if (get_ipv4 == ipv4.is_some()) && (get_ipv6 == ipv6.is_some()) {
-            break;
+    Ok((ipv4, ipv6))
+}
+
+/// Tries OpenDNS's self-address trick first, falling back to Google's TXT
+/// responder. Returns `None` if both fail, so the caller falls through to
+/// the HTTP providers.
+async fn try_get_ipv4_via_dns() -> Option<Ipv4Addr> {
+    match try_get_ipv4_via_opendns().await {
+        Ok(Some(address)) => return Some(address),
+        Ok(None) => log::debug!("OpenDNS A lookup returned no usable address"),
+        Err(error) => log::debug!("OpenDNS A lookup failed: {error:?}"),
+    }
+
+    match try_get_ip_via_google::<Ipv4Addr>(IpAddr::V4(GOOGLE_NS1_V4)).await {
+        Ok(Some(address)) => Some(address),
+        Ok(None) => {
+            log::debug!("Google TXT lookup returned no usable IPv4 address");
+            None
+        }
+        Err(error) => {
+            log::debug!("Google TXT lookup failed: {error:?}");
+            None
         }
     }
+}
 
-    Ok((ipv4, ipv6))
+async fn try_get_ipv6_via_dns() -> Option<Ipv6Addr> {
+    match try_get_ipv6_via_opendns().await {
+        Ok(Some(address)) => return Some(address),
+        Ok(None) => log::debug!("OpenDNS AAAA lookup returned no usable address"),
+        Err(error) => log::debug!("OpenDNS AAAA lookup failed: {error:?}"),
+    }
+
+    match try_get_ip_via_google::<Ipv6Addr>(IpAddr::V6(GOOGLE_NS1_V6)).await {
+        Ok(Some(address)) => Some(address),
+        Ok(None) => {
+            log::debug!("Google TXT lookup returned no usable IPv6 address");
+            None
+        }
+        Err(error) => {
+            log::debug!("Google TXT lookup failed: {error:?}");
+            None
+        }
+    }
+}
+
+async fn try_get_ipv4_via_opendns() -> Result<Option<Ipv4Addr>> {
+    let resolver = resolver_for(&OPENDNS_V4.map(IpAddr::V4))?;
+    let response = resolver
+        .ipv4_lookup("myip.opendns.com.")
+        .await
+        .context("Failed to send OpenDNS A query")?;
+    Ok(response.iter().next().map(|record| record.0))
+}
+
+async fn try_get_ipv6_via_opendns() -> Result<Option<Ipv6Addr>> {
+    let resolver = resolver_for(&[IpAddr::V6(OPENDNS_V6)])?;
+    let response = resolver
+        .ipv6_lookup("myip.opendns.com.")
+        .await
+        .context("Failed to send OpenDNS AAAA query")?;
+    Ok(response.iter().next().map(|record| record.0))
+}
+
+async fn try_get_ip_via_google<Addr: FromStr>(nameserver: IpAddr) -> Result<Option<Addr>> {
+    let resolver = resolver_for(&[nameserver])?;
+    let response = resolver
+        .txt_lookup("o-o.myaddr.l.google.com.")
+        .await
+        .context("Failed to send Google TXT query")?;
+    for record in response.iter() {
+        let data = record
+            .txt_data()
+            .iter()
+            .map(|bytes| String::from_utf8_lossy(bytes))
+            .collect::<String>();
+        if let Ok(address) = data.parse::<Addr>() {
+            return Ok(Some(address));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Builds a resolver that only talks to `nameservers`.
+fn resolver_for(nameservers: &[IpAddr]) -> Result<TokioAsyncResolver> {
+    let group = NameServerConfigGroup::from_ips_clear(nameservers, 53, true);
+    let config = ResolverConfig::from_parts(None, vec![], group);
+    Ok(TokioAsyncResolver::tokio(config, ResolverOpts::default()))
 }
 
 async fn try_get_ip<Addr: FromStr>(
@@ -86,7 +203,7 @@ mod test {
     #[tokio::test]
     async fn test_get_ips() {
         setup();
-        let (ipv4, ipv6) = get_ips(true, true).await.unwrap();
+        let (ipv4, ipv6) = get_ips(true, true, false).await.unwrap();
         println!("ipv4: {:?}", ipv4.unwrap());
         println!("ipv6: {:?}", ipv6.unwrap());
     }
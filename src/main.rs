@@ -1,23 +1,45 @@
 use std::{
+    collections::HashMap,
     net::{Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
     time::Duration,
 };
 
 use anyhow::{bail, Context, Result};
-use api::ApiClient;
-use clap::Parser;
+use api::{ApiClient, ApiError};
+use clap::{Parser, Subcommand};
+use config::{Config, RecordConfig, RecordKind};
 use ip::get_ips;
+use tabled::Table;
 use tokio::time::sleep;
 
 mod api;
+mod config;
 mod ip;
+mod notify;
+mod verify;
 
 #[derive(Parser)]
 #[command(
     version,
     long_about = "Update DNS record with the current IP addresses on DigitalOcean Domains. Supply the DigitalOcean API token via the environment variable DIGITALOCEAN_TOKEN."
 )]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Keep one or more records updated with the host's current IP addresses
+    Run(RunArgs),
+
+    /// Print the records that currently exist for a domain
+    List(ListArgs),
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
     /// Don't actually change anything, just log changes
     #[clap(short = 'n', long)]
     dry_run: bool,
@@ -26,6 +48,10 @@ struct Args {
     #[clap(short = 'o', long)]
     once: bool,
 
+    /// Path to a TOML config file listing the records to manage [default: the single record described by -4/-6/--ttl/--subdomain/domain]
+    #[clap(short = 'c', long)]
+    config: Option<PathBuf>,
+
     /// Create and update A record
     #[clap(short = '4', long)]
     ipv4: bool,
@@ -46,8 +72,71 @@ struct Args {
     #[clap(short = 's', long, default_value = "@")]
     subdomain: String,
 
+    /// Detect the public IP via DNS (OpenDNS/Google) instead of the HTTP
+    /// providers, falling back to HTTP if DNS detection fails
+    #[clap(short = 'd', long)]
+    dns_ip_detection: bool,
+
+    /// After updating or creating a record, query its authoritative
+    /// nameservers directly to confirm the change actually propagated
+    #[clap(short = 'p', long)]
+    verify_propagation: bool,
+
     /// The domain to update
+    domain: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct ListArgs {
+    /// The domain to list records for
     domain: String,
+
+    /// Only show records of this type (A/AAAA/CNAME/TXT/...)
+    #[clap(short = 't', long)]
+    kind: Option<String>,
+}
+
+/// Builds the record list to manage: `--config` if given, otherwise a single
+/// record implied by the legacy `-4`/`-6`/`--ttl`/`--subdomain`/`domain` flags.
+fn load_config(args: &RunArgs) -> Result<Config> {
+    if let Some(path) = &args.config {
+        return Config::load(path);
+    }
+
+    let domain = args
+        .domain
+        .clone()
+        .context("Either --config or a domain argument must be given")?;
+    if !args.ipv4 && !args.ipv6 {
+        bail!("At least one of -4 or -6 must be specified");
+    }
+
+    let mut records = Vec::new();
+    if args.ipv4 {
+        records.push(RecordConfig {
+            domain: domain.clone(),
+            subdomain: args.subdomain.clone(),
+            kind: RecordKind::A,
+            ttl: args.ttl,
+        });
+    }
+    if args.ipv6 {
+        records.push(RecordConfig {
+            domain: domain.clone(),
+            subdomain: args.subdomain.clone(),
+            kind: RecordKind::Aaaa,
+            ttl: args.ttl,
+        });
+    }
+
+    Ok(Config {
+        records,
+        notify: None,
+        requests_per_minute: None,
+        dns_ip_detection: args.dns_ip_detection,
+        verify_propagation: args.verify_propagation,
+        verify_propagation_timeout_secs: 30,
+    })
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -57,24 +146,30 @@ async fn main() -> Result<()> {
     }
     env_logger::init();
 
-    let args = Args::parse();
-    if !args.ipv4 && !args.ipv6 {
-        bail!("At least one of -4 or -6 must be specified");
+    match Cli::parse().command {
+        Command::Run(args) => run(args).await,
+        Command::List(args) => list(args).await,
     }
+}
+
+async fn run(args: RunArgs) -> Result<()> {
     if args.ttl == 0 {
         bail!("TTL must be greater than 0");
     }
     if args.sleep_interval == 0 {
         bail!("Sleep interval must be greater than 0");
     }
+    let config = load_config(&args)?;
 
     let token = get_token()?;
-    let client = ApiClient::new(&token);
+    let client = match config.requests_per_minute {
+        Some(rpm) => ApiClient::with_requests_per_minute(&token, rpm),
+        None => ApiClient::new(&token),
+    };
     let sleep_interval = Duration::from_secs(args.sleep_interval);
-    let mut last_ipv4 = None;
-    let mut last_ipv6 = None;
+    let mut last_addresses = HashMap::new();
     loop {
-        if let Err(error) = dyndns(&args, &client, &mut last_ipv4, &mut last_ipv6).await {
+        if let Err(error) = dyndns(&args, &config, &client, &mut last_addresses).await {
             log::error!("{error:?}");
         }
 
@@ -88,13 +183,36 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Prints the records for `args.domain` (optionally filtered by `args.kind`)
+/// as an aligned table, so users can inspect what exists before wiring up
+/// updates or debug the "more than one record found" error from `handle_record`.
+/// Only the first page of up to 200 records is fetched.
+async fn list(args: ListArgs) -> Result<()> {
+    let token = get_token()?;
+    let client = ApiClient::new(&token);
+    let records = client
+        .get_records(&args.domain, Some(200), args.kind.as_deref(), None)
+        .await
+        .context("Failed to get records")?;
+
+    println!("{}", Table::new(records));
+
+    Ok(())
+}
+
+/// The last seen addresses for a single configured record, keyed by
+/// `(domain, subdomain, kind)` so distinct records don't clobber each other.
+type LastAddresses = HashMap<(String, String, RecordKind), (Option<Ipv4Addr>, Option<Ipv6Addr>)>;
+
 async fn dyndns(
-    args: &Args,
+    args: &RunArgs,
+    config: &Config,
     client: &ApiClient,
-    last_ipv4: &mut Option<Ipv4Addr>,
-    last_ipv6: &mut Option<Ipv6Addr>,
+    last_addresses: &mut LastAddresses,
 ) -> Result<()> {
-    let (ipv4, ipv6) = get_ips(args.ipv4, args.ipv6)
+    let need_ipv4 = config.records.iter().any(|record| record.wants_ipv4());
+    let need_ipv6 = config.records.iter().any(|record| record.wants_ipv6());
+    let (ipv4, ipv6) = get_ips(need_ipv4, need_ipv6, config.dns_ip_detection)
         .await
         .context("Failed to get IP addresses")?;
     if let Some(ipv4) = &ipv4 {
@@ -104,132 +222,270 @@ async fn dyndns(
         log::debug!("Current IPv6 address: {ipv6}");
     }
 
-    if args.ipv4 && ipv4.is_none() {
+    if need_ipv4 && ipv4.is_none() {
         bail!("No IPv4 address found");
     }
-    if args.ipv6 && ipv6.is_none() {
+    if need_ipv6 && ipv6.is_none() {
         bail!("No IPv6 address found");
     }
 
-    if args.ipv4 && &ipv4 != last_ipv4 {
-        if let Some(ipv4) = &ipv4 {
-            log::info!("New IPv4 address: {ipv4}");
-            handle_a_record(args, ipv4, client)
-                .await
-                .context("Failed to update or create A record")?;
-            *last_ipv4 = Some(*ipv4);
-        } else {
-            log::warn!("No IPv4 address found");
+    for record in &config.records {
+        let key = (record.domain.clone(), record.subdomain.clone(), record.kind);
+        let (last_ipv4, last_ipv6) = last_addresses.entry(key).or_insert((None, None));
+
+        if record.wants_ipv4() && ipv4 != *last_ipv4 {
+            if let Some(addr) = ipv4 {
+                log::info!("New IPv4 address for {}: {addr}", record_label(record));
+                let old = last_ipv4.map(|addr| addr.to_string());
+                let update = handle_record(args, record, &addr.to_string(), client)
+                    .await
+                    .context("Failed to update or create A record")?;
+                *last_ipv4 = Some(addr);
+                if update.is_write() {
+                    verify_record_propagation(config, record, &addr.to_string()).await;
+                    notify_address_change(config, record, old.as_deref(), &addr.to_string());
+                }
+            } else {
+                log::warn!("No IPv4 address found");
+            }
         }
-    }
 
-    if args.ipv6 && &ipv6 != last_ipv6 {
-        if let Some(ipv6) = &ipv6 {
-            log::info!("New IPv6 address: {ipv6}");
-            handle_aaaa_record(args, ipv6, client)
-                .await
-                .context("Failed to update or create AAAA record")?;
-            *last_ipv6 = Some(*ipv6);
-        } else {
-            log::warn!("No IPv6 address found");
+        if record.wants_ipv6() && ipv6 != *last_ipv6 {
+            if let Some(addr) = ipv6 {
+                log::info!("New IPv6 address for {}: {addr}", record_label(record));
+                let old = last_ipv6.map(|addr| addr.to_string());
+                let update = handle_record(args, record, &addr.to_string(), client)
+                    .await
+                    .context("Failed to update or create AAAA record")?;
+                *last_ipv6 = Some(addr);
+                if update.is_write() {
+                    verify_record_propagation(config, record, &addr.to_string()).await;
+                    notify_address_change(config, record, old.as_deref(), &addr.to_string());
+                }
+            } else {
+                log::warn!("No IPv6 address found");
+            }
         }
     }
 
     Ok(())
 }
 
+/// Emails a change notification if `[notify]` is configured. Failures here
+/// and in [`verify_record_propagation`] are only logged, matching the
+/// fail-soft policy [`notify::notify_address_change`] documents.
+fn notify_address_change(config: &Config, record: &RecordConfig, old: Option<&str>, new: &str) {
+    let Some(notify_config) = &config.notify else {
+        return;
+    };
+
+    if let Err(error) = notify::notify_address_change(
+        notify_config,
+        &record.domain,
+        &record.subdomain,
+        record.kind.as_str(),
+        old,
+        new,
+    ) {
+        log::warn!("Failed to send change notification: {error:?}");
+    }
+}
+
+/// Confirms a just-written record actually propagated to its authoritative
+/// nameservers, if `verify_propagation` is enabled.
+async fn verify_record_propagation(config: &Config, record: &RecordConfig, addr: &str) {
+    if !config.verify_propagation {
+        return;
+    }
+
+    let timeout = Duration::from_secs(config.verify_propagation_timeout_secs);
+    match verify::verify_propagation(
+        &record.domain,
+        &record.subdomain,
+        record.kind.as_str(),
+        addr,
+        timeout,
+    )
+    .await
+    {
+        Ok(true) => log::info!("{} propagated successfully", record_label(record)),
+        Ok(false) => log::warn!(
+            "{} did not propagate within {}s",
+            record_label(record),
+            timeout.as_secs()
+        ),
+        Err(error) => log::warn!("Failed to verify propagation of {}: {error:?}", record_label(record)),
+    }
+}
+
+fn record_label(record: &RecordConfig) -> String {
+    format!(
+        "{} record {}.{}",
+        record.kind.as_str(),
+        record.subdomain,
+        record.domain
+    )
+}
+
 fn get_token() -> Result<String> {
     std::env::var("DIGITALOCEAN_TOKEN").context("DIGITALOCEAN_TOKEN not set or invalid UTF-8")
 }
 
-async fn handle_a_record(args: &Args, addr: &Ipv4Addr, client: &ApiClient) -> Result<()> {
-    handle_record(args, "A", &addr.to_string(), client).await
+/// How many times to retry a request after [`ApiError::RateLimited`] before
+/// giving up.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Runs `request`, retrying while it fails with [`ApiError::RateLimited`] by
+/// sleeping until the quota resets. Any other error, or a rate limit that
+/// persists past [`MAX_RATE_LIMIT_RETRIES`], is returned as-is.
+async fn with_rate_limit_retry<T, F, Fut>(mut request: F) -> Result<T, ApiError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, ApiError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Err(ApiError::RateLimited { remaining, reset }) if attempt < MAX_RATE_LIMIT_RETRIES => {
+                attempt += 1;
+                let wait = seconds_until(reset);
+                log::warn!(
+                    "DigitalOcean API rate limit hit (remaining: {remaining}), retrying in {wait}s"
+                );
+                sleep(Duration::from_secs(wait)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn seconds_until(unix_time: u64) -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    unix_time.saturating_sub(now).max(1)
 }
 
-async fn handle_aaaa_record(args: &Args, addr: &Ipv6Addr, client: &ApiClient) -> Result<()> {
-    handle_record(args, "AAAA", &addr.to_string(), client).await
+/// What [`handle_record`] actually did, so callers can tell a real write
+/// from a no-op or a `--dry-run` log line apart and skip notifying or
+/// verifying propagation of a change that never happened.
+enum RecordUpdate {
+    Created,
+    Updated,
+    Unchanged,
+    WouldChange,
 }
 
-async fn handle_record(args: &Args, kind: &str, addr: &str, client: &ApiClient) -> Result<()> {
-    let name_filter = match args.subdomain.as_str() {
-        "@" => Some(args.domain.clone()),
-        name => Some(format!("{name}.{}", args.domain)),
+impl RecordUpdate {
+    fn is_write(&self) -> bool {
+        matches!(self, RecordUpdate::Created | RecordUpdate::Updated)
+    }
+}
+
+async fn handle_record(
+    args: &RunArgs,
+    record: &RecordConfig,
+    addr: &str,
+    client: &ApiClient,
+) -> Result<RecordUpdate> {
+    let kind = record.kind.as_str();
+    let name_filter = match record.subdomain.as_str() {
+        "@" => Some(record.domain.clone()),
+        name => Some(format!("{name}.{}", record.domain)),
     };
-    let records = client
-        .get_records(&args.domain, Some(200), Some(kind), name_filter.as_deref())
-        .await
-        .with_context(|| format!("Failed to get {kind} records"))?
-        .into_iter()
-        .filter(|record| record.name == args.subdomain)
-        .collect::<Vec<_>>();
+    let records = with_rate_limit_retry(|| {
+        client.get_records(&record.domain, Some(200), Some(kind), name_filter.as_deref())
+    })
+    .await
+    .with_context(|| format!("Failed to get {kind} records"))?
+    .into_iter()
+    .filter(|r| r.name == record.subdomain)
+    .collect::<Vec<_>>();
+    if let Some(rate_limit) = client.rate_limit() {
+        log::debug!("DigitalOcean API quota remaining: {}", rate_limit.remaining);
+    }
 
-    let name = &args.subdomain;
+    let name = &record.subdomain;
     let data = addr.to_string();
-    let ttl = args.ttl;
+    let ttl = record.ttl;
     match records.len() {
-        0 => create_record(client, args, name, kind, &data, ttl)
-            .await
-            .with_context(|| format!("Failed to create {kind} record"))?,
+        0 => {
+            let created = create_record(client, args, record, name, kind, &data, ttl)
+                .await
+                .with_context(|| format!("Failed to create {kind} record"))?;
+            Ok(if created {
+                RecordUpdate::Created
+            } else {
+                RecordUpdate::WouldChange
+            })
+        }
         1 => {
-            let record = records.first().unwrap();
-            if record.data == data {
+            let existing = records.first().unwrap();
+            if existing.data == data {
                 log::info!("{kind} record is up to date");
-                return Ok(());
+                return Ok(RecordUpdate::Unchanged);
             }
 
-            update_record(client, args, record.id, name, kind, &data, ttl)
+            let updated = update_record(client, args, record, existing.id, name, kind, &data, ttl)
                 .await
                 .with_context(|| format!("Failed to update {kind} record"))?;
+            Ok(if updated {
+                RecordUpdate::Updated
+            } else {
+                RecordUpdate::WouldChange
+            })
         }
         _ => {
             bail!("More than one {kind} record found");
         }
     }
-
-    Ok(())
 }
 
+/// Creates `record`, unless `--dry-run` only logs what would be sent.
+/// Returns whether the record was actually created.
 async fn create_record(
     client: &ApiClient,
-    args: &Args,
+    args: &RunArgs,
+    record: &RecordConfig,
     name: &str,
     kind: &str,
     data: &str,
     ttl: u32,
-) -> Result<()> {
+) -> Result<bool> {
     log::info!("Creating new {kind} record");
     if args.dry_run {
         log::info!(
             "Would create record: {{ name: {name:?}, type: {kind:?}, data: {data:?}, ttl: {ttl} }}"
         );
-    } else {
-        client
-            .create_record(&args.domain, name, kind, data, ttl)
-            .await?;
+        return Ok(false);
     }
 
-    Ok(())
+    with_rate_limit_retry(|| client.create_record(&record.domain, name, kind, data, ttl)).await?;
+    Ok(true)
 }
 
+/// Updates `record`, unless `--dry-run` only logs what would be sent.
+/// Returns whether the record was actually updated.
 async fn update_record(
     client: &ApiClient,
-    args: &Args,
+    args: &RunArgs,
+    record: &RecordConfig,
     id: i64,
     name: &str,
     kind: &str,
     data: &str,
     ttl: u32,
-) -> Result<()> {
+) -> Result<bool> {
     log::info!("Updating existing {kind} record");
     if args.dry_run {
         log::info!(
             "Would update record: {{ name: {name:?}, type: {kind:?}, data: {data:?}, ttl: {ttl} }}"
         );
-    } else {
-        client
-            .update_record(&args.domain, id, name, kind, data, ttl)
-            .await?;
+        return Ok(false);
     }
 
-    Ok(())
+    with_rate_limit_retry(|| client.update_record(&record.domain, id, name, kind, data, ttl))
+        .await?;
+    Ok(true)
 }
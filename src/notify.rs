@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use lettre::{
+    message::Message, transport::smtp::authentication::Credentials, SmtpTransport, Transport,
+};
+
+use crate::config::NotifyConfig;
+
+/// The implicit-TLS (SMTPS) port; any other configured port is assumed to
+/// speak STARTTLS instead.
+const SMTPS_PORT: u16 = 465;
+
+/// Emails `config.to` that `kind` record `subdomain`.`domain` changed from
+/// `old` to `new`. Errors are the caller's to decide whether to treat as
+/// fatal; `dyndns` treats a failed send as a soft failure.
+pub fn notify_address_change(
+    config: &NotifyConfig,
+    domain: &str,
+    subdomain: &str,
+    kind: &str,
+    old: Option<&str>,
+    new: &str,
+) -> Result<()> {
+    let old = old.unwrap_or("unknown");
+    let subject = format!("do_dyndns: {kind} record {subdomain}.{domain} updated");
+    let body = format!(
+        "The {kind} record for {subdomain}.{domain} changed from {old} to {new}.",
+    );
+
+    let message = Message::builder()
+        .from(config.from.parse().context("Invalid notify.from address")?)
+        .to(config.to.parse().context("Invalid notify.to address")?)
+        .subject(subject)
+        .body(body)
+        .context("Failed to build notification email")?;
+
+    let credentials = Credentials::new(config.username.clone(), config.password.clone());
+    let builder = if config.port == SMTPS_PORT {
+        SmtpTransport::relay(&config.server)
+    } else {
+        SmtpTransport::starttls_relay(&config.server)
+    }
+    .context("Failed to configure SMTP relay")?;
+    let mailer = builder.port(config.port).credentials(credentials).build();
+
+    mailer
+        .send(&message)
+        .context("Failed to send notification email")?;
+
+    Ok(())
+}
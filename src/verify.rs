@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    proto::rr::RecordType,
+    TokioAsyncResolver,
+};
+use tokio::time::sleep;
+
+/// How often to retry the authoritative lookup while waiting for propagation.
+const RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Queries `subdomain.domain`'s authoritative nameservers directly, retrying
+/// every [`RETRY_INTERVAL`] until the answer matches `expected` or `timeout`
+/// elapses. Returns `Ok(false)` (not an error) if it never matched in time.
+pub async fn verify_propagation(
+    domain: &str,
+    subdomain: &str,
+    kind: &str,
+    expected: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    let record_type: RecordType = kind
+        .parse()
+        .with_context(|| format!("Unsupported record type {kind}"))?;
+    let authoritative = authoritative_resolver(domain).await?;
+
+    let name = match subdomain {
+        "@" => format!("{domain}."),
+        name => format!("{name}.{domain}."),
+    };
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        match authoritative.lookup(&name, record_type).await {
+            Ok(answer) => {
+                if answer.iter().any(|rdata| rdata.to_string() == expected) {
+                    return Ok(true);
+                }
+            }
+            Err(error) => log::debug!("Authoritative lookup for {name} failed: {error:?}"),
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Ok(false);
+        }
+        sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// Resolves `domain`'s NS records, then builds a resolver pinned directly to
+/// the first authoritative nameserver's address.
+async fn authoritative_resolver(domain: &str) -> Result<TokioAsyncResolver> {
+    let recursive = TokioAsyncResolver::tokio(ResolverConfig::default(), uncached_opts());
+    let ns_records = recursive
+        .ns_lookup(format!("{domain}."))
+        .await
+        .with_context(|| format!("Failed to resolve NS records for {domain}"))?;
+    let nameserver = ns_records
+        .iter()
+        .next()
+        .with_context(|| format!("{domain} has no NS records"))?
+        .to_string();
+
+    let nameserver_ips = recursive
+        .lookup_ip(&nameserver)
+        .await
+        .with_context(|| format!("Failed to resolve authoritative nameserver {nameserver}"))?
+        .iter()
+        .collect::<Vec<_>>();
+    if nameserver_ips.is_empty() {
+        bail!("Authoritative nameserver {nameserver} has no address");
+    }
+
+    let group = NameServerConfigGroup::from_ips_clear(&nameserver_ips, 53, true);
+    let config = ResolverConfig::from_parts(None, vec![], group);
+    Ok(TokioAsyncResolver::tokio(config, uncached_opts()))
+}
+
+/// Resolver options with caching disabled, so [`verify_propagation`]'s retry
+/// loop re-queries the nameserver on every attempt instead of replaying a
+/// cached answer for the record's TTL.
+fn uncached_opts() -> ResolverOpts {
+    let mut opts = ResolverOpts::default();
+    opts.cache_size = 0;
+    opts.positive_max_ttl = Some(Duration::from_secs(0));
+    opts.negative_max_ttl = Some(Duration::from_secs(0));
+    opts
+}